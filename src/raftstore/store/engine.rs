@@ -13,18 +13,224 @@
 
 use std::option::Option;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::str::{self, FromStr};
+use std::sync::{Arc, Condvar, Mutex};
 use std::fmt::{self, Debug, Formatter};
+use std::collections::HashMap;
 
 use rocksdb::{CFHandle, DBIterator, DBVector, ReadOptions, Writable, WriteBatch, DB};
 use rocksdb::rocksdb_options::UnsafeSnap;
 use protobuf;
 use byteorder::{BigEndian, ByteOrder};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use futures::Future;
+use futures_cpupool::CpuPool;
 use util::rocksdb;
 
 use raftstore::Result;
 use raftstore::Error;
 
+/// The set of byte encodings that `Peekable::get_as` / `Mutable::put_as`
+/// understand. All but `TimestampFmt`/`TimestampTZFmt` have a short name
+/// (see the `FromStr` impl) so they can be picked via config.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conversion {
+    /// Raw bytes, no decoding.
+    Bytes,
+    /// Big-endian 8-byte integer, see `get_u64`/`get_i64`.
+    Integer,
+    /// Big-endian 8-byte IEEE-754 float.
+    Float,
+    /// A single `0`/`1` byte.
+    Boolean,
+    /// Big-endian 8-byte Unix timestamp, in seconds.
+    Timestamp,
+    /// A timestamp formatted with the given `strftime` pattern, without a
+    /// time zone offset.
+    TimestampFmt(String),
+    /// Like `TimestampFmt`, but the pattern includes an explicit time zone
+    /// offset.
+    TimestampTZFmt(String),
+}
+
+impl FromStr for Conversion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Conversion> {
+        match s {
+            "int" | "integer" => Ok(Conversion::Integer),
+            "float" => Ok(Conversion::Float),
+            "bool" | "boolean" => Ok(Conversion::Boolean),
+            "timestamp" => Ok(Conversion::Timestamp),
+            "asis" | "bytes" => Ok(Conversion::Bytes),
+            _ => Err(box_err!("unsupported conversion: {:?}", s)),
+        }
+    }
+}
+
+/// A value decoded (or to be encoded) according to a `Conversion`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypedValue {
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Timestamp(i64),
+    TimestampFmt(NaiveDateTime, String),
+    TimestampTZFmt(DateTime<FixedOffset>, String),
+}
+
+fn decode_typed_value(value: &[u8], conv: &Conversion) -> Result<TypedValue> {
+    match *conv {
+        Conversion::Bytes => Ok(TypedValue::Bytes(value.to_vec())),
+        Conversion::Integer => {
+            if value.len() != 8 {
+                return Err(box_err!("need 8 bytes, but only got {}", value.len()));
+            }
+            Ok(TypedValue::Integer(BigEndian::read_i64(value)))
+        }
+        Conversion::Float => {
+            if value.len() != 8 {
+                return Err(box_err!("need 8 bytes, but only got {}", value.len()));
+            }
+            Ok(TypedValue::Float(f64::from_bits(BigEndian::read_u64(value))))
+        }
+        Conversion::Boolean => {
+            if value.len() != 1 {
+                return Err(box_err!("need 1 byte, but only got {}", value.len()));
+            }
+            Ok(TypedValue::Boolean(value[0] != 0))
+        }
+        Conversion::Timestamp => {
+            if value.len() != 8 {
+                return Err(box_err!("need 8 bytes, but only got {}", value.len()));
+            }
+            Ok(TypedValue::Timestamp(BigEndian::read_i64(value)))
+        }
+        Conversion::TimestampFmt(ref fmt) => {
+            let s = try!(str::from_utf8(value)
+                .map_err(|e| box_err!("invalid utf8 timestamp: {:?}", e)));
+            let dt = try!(NaiveDateTime::parse_from_str(s, fmt)
+                .map_err(|e| box_err!("invalid timestamp {:?}: {:?}", s, e)));
+            Ok(TypedValue::TimestampFmt(dt, fmt.clone()))
+        }
+        Conversion::TimestampTZFmt(ref fmt) => {
+            let s = try!(str::from_utf8(value)
+                .map_err(|e| box_err!("invalid utf8 timestamp: {:?}", e)));
+            let dt = try!(DateTime::parse_from_str(s, fmt)
+                .map_err(|e| box_err!("invalid timestamp {:?}: {:?}", s, e)));
+            Ok(TypedValue::TimestampTZFmt(dt, fmt.clone()))
+        }
+    }
+}
+
+fn encode_typed_value(value: &TypedValue) -> Vec<u8> {
+    match *value {
+        TypedValue::Bytes(ref b) => b.clone(),
+        TypedValue::Integer(n) => {
+            let mut buf = vec![0; 8];
+            BigEndian::write_i64(&mut buf, n);
+            buf
+        }
+        TypedValue::Float(f) => {
+            let mut buf = vec![0; 8];
+            BigEndian::write_u64(&mut buf, f.to_bits());
+            buf
+        }
+        TypedValue::Boolean(b) => vec![if b { 1 } else { 0 }],
+        TypedValue::Timestamp(n) => {
+            let mut buf = vec![0; 8];
+            BigEndian::write_i64(&mut buf, n);
+            buf
+        }
+        TypedValue::TimestampFmt(ref dt, ref fmt) => dt.format(fmt).to_string().into_bytes(),
+        TypedValue::TimestampTZFmt(ref dt, ref fmt) => dt.format(fmt).to_string().into_bytes(),
+    }
+}
+
+/// The default column family, written to by the CF-less `put`/`get`.
+const CF_DEFAULT: &'static str = "default";
+
+/// What a column family's values are expected to look like: either one of
+/// the `Conversion` encodings, or an opaque protobuf message (named purely
+/// for documentation, since its structure can't be checked without the
+/// concrete message type).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema {
+    Typed(Conversion),
+    Message(String),
+}
+
+impl FromStr for Schema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Schema> {
+        if s.starts_with("msg:") {
+            return Ok(Schema::Message(s[4..].to_owned()));
+        }
+        s.parse().map(Schema::Typed)
+    }
+}
+
+/// A CF name -> `Schema` table, used to validate `Mutable::put_*` writes
+/// and to decode `Peekable::get_typed` reads without the caller naming the
+/// type at the call site.
+#[derive(Clone, Debug, Default)]
+pub struct SchemaRegistry {
+    cfs: HashMap<String, Schema>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> SchemaRegistry {
+        SchemaRegistry { cfs: HashMap::new() }
+    }
+
+    /// Builds a registry from a CF name -> type name table, e.g.
+    /// `{"default": "bytes", "last_applied": "int", "meta": "msg:RegionLocalState"}`,
+    /// as loaded from a config file.
+    pub fn from_config(cfg: &HashMap<String, String>) -> Result<SchemaRegistry> {
+        let mut cfs = HashMap::with_capacity(cfg.len());
+        for (cf, type_name) in cfg {
+            cfs.insert(cf.clone(), try!(type_name.parse()));
+        }
+        Ok(SchemaRegistry { cfs: cfs })
+    }
+
+    fn schema(&self, cf: &str) -> Result<&Schema> {
+        self.cfs
+            .get(cf)
+            .ok_or_else(|| box_err!("no schema registered for cf {:?}", cf))
+    }
+
+    /// The `Conversion` `cf` is declared with. Errs for a CF declared as a
+    /// protobuf message, since those are decoded with `get_msg` instead.
+    fn conversion(&self, cf: &str) -> Result<Conversion> {
+        match *try!(self.schema(cf)) {
+            Schema::Typed(ref conv) => Ok(conv.clone()),
+            Schema::Message(ref proto) => Err(box_err!(
+                "cf {:?} is declared as message {:?}, use get_msg instead",
+                cf,
+                proto
+            )),
+        }
+    }
+
+    /// Checks that `value` decodes under the encoding declared for `cf`, if
+    /// any. A CF with no registered schema is left unchecked, so callers can
+    /// pass a registry covering only the CFs they care to validate; a CF
+    /// declared as a message or as bytes accepts any value.
+    fn check(&self, cf: &str, value: &[u8]) -> Result<()> {
+        let schema = match self.cfs.get(cf) {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+        match *schema {
+            Schema::Message(_) | Schema::Typed(Conversion::Bytes) => Ok(()),
+            Schema::Typed(ref conv) => decode_typed_value(value, conv).map(|_| ()),
+        }
+    }
+}
+
 pub struct Snapshot {
     db: Arc<DB>,
     snap: UnsafeSnap,
@@ -155,6 +361,34 @@ pub trait Peekable {
             Some(n) => Ok(Some(n as i64)),
         }
     }
+
+    fn get_as(&self, key: &[u8], conv: Conversion) -> Result<Option<TypedValue>> {
+        let value = try!(self.get_value(key));
+        match value {
+            None => Ok(None),
+            Some(v) => decode_typed_value(&v, &conv).map(Some),
+        }
+    }
+
+    fn get_as_cf(&self, cf: &str, key: &[u8], conv: Conversion) -> Result<Option<TypedValue>> {
+        let value = try!(self.get_value_cf(cf, key));
+        match value {
+            None => Ok(None),
+            Some(v) => decode_typed_value(&v, &conv).map(Some),
+        }
+    }
+
+    // like `get_as_cf`, but looks up `cf`'s conversion in `schema` instead of
+    // the caller naming it.
+    fn get_typed(
+        &self,
+        schema: &SchemaRegistry,
+        cf: &str,
+        key: &[u8],
+    ) -> Result<Option<TypedValue>> {
+        let conv = try!(schema.conversion(cf));
+        self.get_as_cf(cf, key, conv)
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -361,6 +595,243 @@ impl Iterable for Snapshot {
     }
 }
 
+/// The future type returned by `AsyncPeekable`/`AsyncIterable`. Boxed so the
+/// traits don't leak the `CpuPool` future type they happen to be backed by.
+pub type AsyncResult<T> = Box<Future<Item = T, Error = Error> + Send>;
+
+// TODO: refactor this trait into rocksdb trait.
+//
+// Mirrors `Peekable`, but runs the actual RocksDB read on a dedicated read
+// thread pool instead of the calling (raftstore) thread.
+pub trait AsyncPeekable {
+    fn get_value(&self, key: &[u8]) -> AsyncResult<Option<Vec<u8>>>;
+    fn get_value_cf(&self, cf: &str, key: &[u8]) -> AsyncResult<Option<Vec<u8>>>;
+}
+
+// TODO: refactor this trait into rocksdb trait.
+//
+// Mirrors `Iterable`, but runs the scan on a dedicated read thread pool so
+// the caller can submit it and keep serving other work.
+pub trait AsyncIterable {
+    // like `Iterable::scan`, but runs on the read pool and resolves once `f`
+    // has seen the whole range (or asked to stop).
+    fn scan<F>(&self, start_key: &[u8], end_key: &[u8], fill_cache: bool, f: F) -> AsyncResult<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool> + Send + 'static;
+
+    // like `scan`, only on a specific column family.
+    fn scan_cf<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        f: F,
+    ) -> AsyncResult<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool> + Send + 'static;
+
+    fn seek(&self, key: &[u8]) -> AsyncResult<Option<(Vec<u8>, Vec<u8>)>>;
+    fn seek_cf(&self, cf: &str, key: &[u8]) -> AsyncResult<Option<(Vec<u8>, Vec<u8>)>>;
+
+    // convenience on top of `scan`: collect the whole range into a `Vec`
+    // instead of driving it through a callback.
+    fn scan_collect(
+        &self,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+    ) -> AsyncResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let rows = Arc::new(Mutex::new(Vec::new()));
+        let fut = self.scan(start_key, end_key, fill_cache, collecting_cb(rows.clone()));
+        Box::new(fut.map(move |_| drain_rows(&rows)))
+    }
+
+    // like `scan_collect`, only on a specific column family.
+    fn scan_cf_collect(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+    ) -> AsyncResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let rows = Arc::new(Mutex::new(Vec::new()));
+        let fut = self.scan_cf(
+            cf,
+            start_key,
+            end_key,
+            fill_cache,
+            collecting_cb(rows.clone()),
+        );
+        Box::new(fut.map(move |_| drain_rows(&rows)))
+    }
+}
+
+// a `scan`/`scan_cf` callback that appends every row it sees to `rows`; used
+// by `scan_collect`/`scan_cf_collect` to turn the callback-driven scan into
+// a plain `Vec`.
+fn collecting_cb(
+    rows: Arc<Mutex<Vec<(Vec<u8>, Vec<u8>)>>>,
+) -> Box<FnMut(&[u8], &[u8]) -> Result<bool> + Send> {
+    Box::new(move |k, v| {
+        rows.lock().unwrap().push((k.to_vec(), v.to_vec()));
+        Ok(true)
+    })
+}
+
+// Takes the rows collected so far out of `rows`, without assuming this is
+// the last strong reference: the spawned scan task holds its own clone
+// until the callback returns, which can still be true when the future this
+// runs inside resolves.
+fn drain_rows(rows: &Arc<Mutex<Vec<(Vec<u8>, Vec<u8>)>>>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    rows.lock().unwrap().split_off(0)
+}
+
+// Bounds how many scans can be running against the read pool at once.
+// `CpuPool` only bounds worker threads, not submissions, so without this a
+// burst of `scan_collect` callers can pile unbounded work onto the pool;
+// callers past the cap block (on a pool thread, not the caller's) until a
+// slot frees up instead.
+struct ScanLimiter {
+    available: Mutex<usize>,
+    available_cv: Condvar,
+}
+
+impl ScanLimiter {
+    fn new(max_in_flight: usize) -> ScanLimiter {
+        ScanLimiter {
+            available: Mutex::new(max_in_flight),
+            available_cv: Condvar::new(),
+        }
+    }
+
+    fn acquire(limiter: &Arc<ScanLimiter>) -> ScanPermit {
+        let mut available = limiter.available.lock().unwrap();
+        while *available == 0 {
+            available = limiter.available_cv.wait(available).unwrap();
+        }
+        *available -= 1;
+        ScanPermit { limiter: limiter.clone() }
+    }
+}
+
+// Releases the `ScanLimiter` slot it was handed by `ScanLimiter::acquire`
+// once the scan that held it finishes.
+struct ScanPermit {
+    limiter: Arc<ScanLimiter>,
+}
+
+impl Drop for ScanPermit {
+    fn drop(&mut self) {
+        *self.limiter.available.lock().unwrap() += 1;
+        self.limiter.available_cv.notify_one();
+    }
+}
+
+/// An async-friendly handle onto a point-in-time `Snapshot`: reads and scans
+/// submitted through it run on `pool` instead of the caller's thread, while
+/// the wrapped `Arc<Snapshot>` keeps the snapshot alive for as long as the
+/// async work is in flight.
+#[derive(Clone)]
+pub struct AsyncSnapshot {
+    snap: Arc<Snapshot>,
+    pool: CpuPool,
+    scan_limit: Arc<ScanLimiter>,
+}
+
+impl AsyncSnapshot {
+    // `pool` is shared across snapshots so callers don't spin up a fresh
+    // thread pool for every snapshot they wrap; `max_in_flight_scans` caps
+    // how many scans this snapshot will run against `pool` at once.
+    pub fn new(snap: Arc<Snapshot>, pool: CpuPool, max_in_flight_scans: usize) -> AsyncSnapshot {
+        AsyncSnapshot {
+            snap: snap,
+            pool: pool,
+            scan_limit: Arc::new(ScanLimiter::new(max_in_flight_scans)),
+        }
+    }
+}
+
+impl AsyncPeekable for AsyncSnapshot {
+    fn get_value(&self, key: &[u8]) -> AsyncResult<Option<Vec<u8>>> {
+        let snap = self.snap.clone();
+        let key = key.to_vec();
+        // `DBVector` borrows RocksDB-owned memory through a raw pointer and
+        // isn't `Send`, so copy the bytes out before they leave the pool
+        // thread.
+        Box::new(
+            self.pool
+                .spawn_fn(move || snap.get_value(&key).map(|v| v.map(|v| v.to_vec()))),
+        )
+    }
+
+    fn get_value_cf(&self, cf: &str, key: &[u8]) -> AsyncResult<Option<Vec<u8>>> {
+        let snap = self.snap.clone();
+        let cf = cf.to_owned();
+        let key = key.to_vec();
+        Box::new(self.pool.spawn_fn(move || {
+            snap.get_value_cf(&cf, &key).map(|v| v.map(|v| v.to_vec()))
+        }))
+    }
+}
+
+impl AsyncIterable for AsyncSnapshot {
+    fn scan<F>(
+        &self,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        mut f: F,
+    ) -> AsyncResult<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool> + Send + 'static,
+    {
+        let snap = self.snap.clone();
+        let start_key = start_key.to_vec();
+        let end_key = end_key.to_vec();
+        let scan_limit = self.scan_limit.clone();
+        Box::new(self.pool.spawn_fn(move || {
+            let _permit = ScanLimiter::acquire(&scan_limit);
+            snap.scan(&start_key, &end_key, fill_cache, &mut f)
+        }))
+    }
+
+    fn scan_cf<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        mut f: F,
+    ) -> AsyncResult<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool> + Send + 'static,
+    {
+        let snap = self.snap.clone();
+        let cf = cf.to_owned();
+        let start_key = start_key.to_vec();
+        let end_key = end_key.to_vec();
+        let scan_limit = self.scan_limit.clone();
+        Box::new(self.pool.spawn_fn(move || {
+            let _permit = ScanLimiter::acquire(&scan_limit);
+            snap.scan_cf(&cf, &start_key, &end_key, fill_cache, &mut f)
+        }))
+    }
+
+    fn seek(&self, key: &[u8]) -> AsyncResult<Option<(Vec<u8>, Vec<u8>)>> {
+        let snap = self.snap.clone();
+        let key = key.to_vec();
+        Box::new(self.pool.spawn_fn(move || snap.seek(&key)))
+    }
+
+    fn seek_cf(&self, cf: &str, key: &[u8]) -> AsyncResult<Option<(Vec<u8>, Vec<u8>)>> {
+        let snap = self.snap.clone();
+        let cf = cf.to_owned();
+        let key = key.to_vec();
+        Box::new(self.pool.spawn_fn(move || snap.seek_cf(&cf, &key)))
+    }
+}
+
 pub trait Mutable: Writable {
     fn put_msg<M: protobuf::Message>(&self, key: &[u8], m: &M) -> Result<()> {
         let value = try!(m.write_to_bytes());
@@ -389,6 +860,97 @@ pub trait Mutable: Writable {
         try!(self.delete(key));
         Ok(())
     }
+
+    fn put_as(&self, key: &[u8], value: &TypedValue) -> Result<()> {
+        let bytes = encode_typed_value(value);
+        try!(self.put(key, &bytes));
+        Ok(())
+    }
+
+    // like `put_u64`, but rejects the write if the 8-byte value does not
+    // decode under the encoding `schema` declares for the default CF (see
+    // `SchemaRegistry::check`). A CF declared as a float, a timestamp, a
+    // message or bytes accepts it as well.
+    fn put_u64_checked(&self, schema: &SchemaRegistry, key: &[u8], n: u64) -> Result<()> {
+        let mut value = vec![0; 8];
+        BigEndian::write_u64(&mut value, n);
+        try!(schema.check(CF_DEFAULT, &value));
+        try!(self.put(key, &value));
+        Ok(())
+    }
+
+    fn put_i64_checked(&self, schema: &SchemaRegistry, key: &[u8], n: i64) -> Result<()> {
+        self.put_u64_checked(schema, key, n as u64)
+    }
+
+    // like `put_msg`, but rejects the write if the encoded message does not
+    // decode under the encoding `schema` declares for the default CF (see
+    // `SchemaRegistry::check`). A CF declared as a message or as bytes
+    // accepts any message, and so does a typed CF whose encoding the
+    // message bytes happen to decode under.
+    fn put_msg_checked<M: protobuf::Message>(
+        &self,
+        schema: &SchemaRegistry,
+        key: &[u8],
+        m: &M,
+    ) -> Result<()> {
+        let value = try!(m.write_to_bytes());
+        try!(schema.check(CF_DEFAULT, &value));
+        try!(self.put(key, &value));
+        Ok(())
+    }
+
+    // like `put_as`, but rejects the write if `value`'s encoded bytes do not
+    // decode under the encoding `schema` declares for the default CF (see
+    // `SchemaRegistry::check`). The variant of `value` is not compared with
+    // the declared encoding, so e.g. a `Float` is accepted by an `int` CF.
+    fn put_as_checked(
+        &self,
+        schema: &SchemaRegistry,
+        key: &[u8],
+        value: &TypedValue,
+    ) -> Result<()> {
+        let bytes = encode_typed_value(value);
+        try!(schema.check(CF_DEFAULT, &bytes));
+        try!(self.put(key, &bytes));
+        Ok(())
+    }
+
+    // like `put_msg_cf`, but rejects the write if the encoded message does
+    // not decode under the encoding `schema` declares for `cf_name`. A CF
+    // declared as a message or as bytes accepts any message, and so does a
+    // typed CF whose encoding the message bytes happen to decode under.
+    fn put_msg_cf_checked<M: protobuf::Message>(
+        &self,
+        schema: &SchemaRegistry,
+        cf_name: &str,
+        cf: &CFHandle,
+        key: &[u8],
+        m: &M,
+    ) -> Result<()> {
+        let value = try!(m.write_to_bytes());
+        try!(schema.check(cf_name, &value));
+        try!(self.put_cf(cf, key, &value));
+        Ok(())
+    }
+
+    // like `put_as`, but writes a specific CF and rejects the write if
+    // `value`'s encoded bytes do not decode under the encoding `schema`
+    // declares for `cf_name`; the variant of `value` is not compared with
+    // the declared encoding.
+    fn put_as_cf_checked(
+        &self,
+        schema: &SchemaRegistry,
+        cf_name: &str,
+        cf: &CFHandle,
+        key: &[u8],
+        value: &TypedValue,
+    ) -> Result<()> {
+        let bytes = encode_typed_value(value);
+        try!(schema.check(cf_name, &bytes));
+        try!(self.put_cf(cf, key, &bytes));
+        Ok(())
+    }
 }
 
 impl Mutable for DB {}
@@ -486,8 +1048,7 @@ mod tests {
             .scan(b"", &[0xFF, 0xFF], false, &mut |key, value| {
                 data.push((key.to_vec(), value.to_vec()));
                 Ok(true)
-            })
-            .unwrap();
+            }).unwrap();
         assert_eq!(
             data,
             vec![
@@ -501,8 +1062,7 @@ mod tests {
             .scan_cf(cf, b"", &[0xFF, 0xFF], false, &mut |key, value| {
                 data.push((key.to_vec(), value.to_vec()));
                 Ok(true)
-            })
-            .unwrap();
+            }).unwrap();
         assert_eq!(
             data,
             vec![
@@ -525,8 +1085,7 @@ mod tests {
                 data.push((key.to_vec(), value.to_vec()));
                 index += 1;
                 Ok(index != 1)
-            })
-            .unwrap();
+            }).unwrap();
 
         assert_eq!(data.len(), 1);
 
@@ -548,4 +1107,158 @@ mod tests {
 
         assert_eq!(data.len(), 2);
     }
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!("int".parse::<Conversion>().unwrap(), Conversion::Integer);
+        assert_eq!("float".parse::<Conversion>().unwrap(), Conversion::Float);
+        assert_eq!("bool".parse::<Conversion>().unwrap(), Conversion::Boolean);
+        assert_eq!(
+            "timestamp".parse::<Conversion>().unwrap(),
+            Conversion::Timestamp
+        );
+        assert_eq!("asis".parse::<Conversion>().unwrap(), Conversion::Bytes);
+        assert_eq!("bytes".parse::<Conversion>().unwrap(), Conversion::Bytes);
+        assert!("unknown".parse::<Conversion>().is_err());
+    }
+
+    #[test]
+    fn test_get_put_as() {
+        let path = TempDir::new("var").unwrap();
+        let engine = rocksdb::new_engine(path.path().to_str().unwrap(), &[]).unwrap();
+
+        engine.put_as(b"int", &TypedValue::Integer(-42)).unwrap();
+        assert_eq!(
+            engine.get_as(b"int", Conversion::Integer).unwrap(),
+            Some(TypedValue::Integer(-42))
+        );
+
+        engine.put_as(b"float", &TypedValue::Float(3.5)).unwrap();
+        assert_eq!(
+            engine.get_as(b"float", Conversion::Float).unwrap(),
+            Some(TypedValue::Float(3.5))
+        );
+
+        engine.put_as(b"bool", &TypedValue::Boolean(true)).unwrap();
+        assert_eq!(
+            engine.get_as(b"bool", Conversion::Boolean).unwrap(),
+            Some(TypedValue::Boolean(true))
+        );
+
+        engine
+            .put_as(b"ts", &TypedValue::Timestamp(1500000000))
+            .unwrap();
+        assert_eq!(
+            engine.get_as(b"ts", Conversion::Timestamp).unwrap(),
+            Some(TypedValue::Timestamp(1500000000))
+        );
+
+        let missing = engine.get_as(b"missing_key", Conversion::Integer).unwrap();
+        assert!(missing.is_none());
+        assert!(engine.get_as(b"bool", Conversion::Integer).is_err());
+    }
+
+    #[test]
+    fn test_async_snapshot() {
+        let path = TempDir::new("var").unwrap();
+        let cf = "cf";
+        let engine = Arc::new(
+            rocksdb::new_engine(path.path().to_str().unwrap(), &[cf]).unwrap(),
+        );
+        let handle = engine.cf_handle(cf).unwrap();
+
+        engine.put(b"a1", b"v1").unwrap();
+        engine.put(b"a2", b"v2").unwrap();
+        engine.put_cf(handle, b"a1", b"v1").unwrap();
+
+        let snap = Arc::new(Snapshot::new(engine.clone()));
+        let async_snap = AsyncSnapshot::new(snap, CpuPool::new(2), 2);
+
+        assert_eq!(
+            &*async_snap.get_value(b"a1").wait().unwrap().unwrap(),
+            b"v1"
+        );
+        assert_eq!(
+            &*async_snap
+                .get_value_cf(cf, b"a1")
+                .wait()
+                .unwrap()
+                .unwrap(),
+            b"v1"
+        );
+
+        let rows = async_snap
+            .scan_collect(b"", &[0xFF, 0xFF], false)
+            .wait()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (b"a1".to_vec(), b"v1".to_vec()),
+                (b"a2".to_vec(), b"v2".to_vec()),
+            ]
+        );
+
+        let pair = async_snap.seek(b"a1").wait().unwrap().unwrap();
+        assert_eq!(pair, (b"a1".to_vec(), b"v1".to_vec()));
+        assert!(async_snap.seek(b"a3").wait().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!("int".parse::<Schema>().unwrap(), Schema::Typed(Conversion::Integer));
+        assert_eq!(
+            "msg:RegionLocalState".parse::<Schema>().unwrap(),
+            Schema::Message("RegionLocalState".to_owned())
+        );
+        assert!("unknown".parse::<Schema>().is_err());
+
+        let mut cfg = HashMap::new();
+        cfg.insert("default".to_owned(), "int".to_owned());
+        cfg.insert("meta".to_owned(), "msg:RegionLocalState".to_owned());
+        let schema = SchemaRegistry::from_config(&cfg).unwrap();
+
+        assert_eq!(schema.conversion("default").unwrap(), Conversion::Integer);
+        assert!(schema.conversion("meta").is_err());
+        assert!(schema.conversion("unknown_cf").is_err());
+    }
+
+    #[test]
+    fn test_schema_checked_put_get() {
+        let path = TempDir::new("var").unwrap();
+        let cf = "cf";
+        let engine = rocksdb::new_engine(path.path().to_str().unwrap(), &[cf]).unwrap();
+        let handle = engine.cf_handle(cf).unwrap();
+
+        let mut cfg = HashMap::new();
+        cfg.insert("default".to_owned(), "int".to_owned());
+        cfg.insert(cf.to_owned(), "msg:Region".to_owned());
+        let schema = SchemaRegistry::from_config(&cfg).unwrap();
+
+        engine.put_u64_checked(&schema, b"k", 42).unwrap();
+        assert_eq!(
+            engine.get_as(b"k", Conversion::Integer).unwrap(),
+            Some(TypedValue::Integer(42))
+        );
+        assert_eq!(
+            engine.get_typed(&schema, "default", b"k").unwrap(),
+            Some(TypedValue::Integer(42))
+        );
+
+        // "default" is declared as an integer, so a bool-shaped value is
+        // rejected.
+        assert!(
+            engine
+                .put_as_checked(&schema, b"bad", &TypedValue::Boolean(true))
+                .is_err()
+        );
+
+        let mut r = Region::new();
+        r.set_id(7);
+        engine
+            .put_msg_cf_checked(&schema, cf, handle, b"r", &r)
+            .unwrap();
+        let r1: Region = engine.get_msg_cf(cf, b"r").unwrap().unwrap();
+        assert_eq!(r, r1);
+    }
 }